@@ -1,11 +1,15 @@
 use bevy::{
-    prelude::*,
-    sprite::collide_aabb::{collide, Collision},
-    sprite::MaterialMesh2dBundle,
-    utils::Duration,
+    ecs::system::SystemParam, prelude::*, sprite::MaterialMesh2dBundle, utils::Duration,
 };
+use bevy_fundsp::prelude::{
+    envelope, sine_hz, split, AudioUnit32, DspAppExt, DspAudioExt, DspManager, DspPlugin,
+    SourceType, U2,
+};
+use bevy_hanabi::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use rand::Rng;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+use std::fs;
 
 //player
 const PLAYER_SIZE: Vec3 = Vec3::new(120.0, 20.0, 0.0);
@@ -25,17 +29,18 @@ const INITIAL_BALL_DIRECTION: Vec2 = Vec2::new(0.5, -0.5);
 
 //walls
 const WALL_THICKNESS: f32 = 10.0;
-const WALL_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
 
 const LEFT_WALL: f32 = -450.0;
 const RIGHT_WALL: f32 = 450.0;
 const BOTTOM_WALL: f32 = -300.0;
 const TOP_WALL: f32 = 300.0;
 
-//enemies
-const ENEMY_SIZE: Vec3 = Vec3::new(60.0, 20.0, 1.0);
-const STARTING_ENEMY_POSITION: Vec3 = Vec3::new(-350.0, 250.0, 0.0);
-const ENEMY_COLOR: Color = Color::rgb(0.96, 0.55, 0.54);
+//bricks
+const BRICK_SIZE: Vec2 = Vec2::new(100.0, 30.0);
+const GAP_BETWEEN_BRICKS: f32 = 5.0;
+const GAP_BETWEEN_BRICKS_AND_CEILING: f32 = 20.0;
+const GAP_BETWEEN_BRICKS_AND_SIDES: f32 = 20.0;
+const GAP_BETWEEN_PADDLE_AND_BRICKS: f32 = 270.0;
 
 //particles
 const PARTICLE_COLOR: Color = Color::rgb(0.46, 0.78, 0.47);
@@ -43,39 +48,73 @@ const PARTICLE_SIZE: Vec3 = Vec3::new(10.0, 10.0, 1.0);
 const PARTICLE_LIFETIME: u64 = 100;
 const SCALING_FACTOR: f32 = 0.9;
 
+//audio
+const BRICK_TONE_FREQS: [f32; 6] = [261.63, 293.66, 329.63, 392.00, 440.00, 523.25];
+const PADDLE_BOUNCE_FREQ: f32 = 130.81;
+const TONE_DURATION: f32 = 0.3;
+
 //other
 const TIME_STEP: f32 = 1.0 / 60.0;
 const BACKGROUND_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
 
+//levels
+const LEVELS_DIR: &str = "assets/levels";
+
+//lives
+const STARTING_LIVES: u32 = 3;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum AppState {
     InGame,
     Paused,
+    Win,
 }
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugin(WorldInspectorPlugin)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+        .add_plugin(HanabiPlugin)
+        .add_plugin(DspPlugin::default())
+        .add_dsp_source(brick_tone_0, SourceType::Static { duration: TONE_DURATION })
+        .add_dsp_source(brick_tone_1, SourceType::Static { duration: TONE_DURATION })
+        .add_dsp_source(brick_tone_2, SourceType::Static { duration: TONE_DURATION })
+        .add_dsp_source(brick_tone_3, SourceType::Static { duration: TONE_DURATION })
+        .add_dsp_source(brick_tone_4, SourceType::Static { duration: TONE_DURATION })
+        .add_dsp_source(brick_tone_5, SourceType::Static { duration: TONE_DURATION })
+        .add_dsp_source(paddle_bounce_tone, SourceType::Static { duration: TONE_DURATION })
         .insert_resource(ClearColor(BACKGROUND_COLOR))
+        .insert_resource(RapierConfiguration {
+            gravity: Vec2::ZERO,
+            ..default()
+        })
         .add_state(AppState::InGame)
         .add_startup_system(setup)
-        .add_event::<CollisionEvent>()
         .add_event::<GameOverEvent>()
+        .add_event::<LifeLostEvent>()
+        .add_event::<FullResetEvent>()
+        .add_event::<LoadLevelEvent>()
         .add_system(bevy::window::close_on_esc)
         .add_system_set(
             SystemSet::on_update(AppState::InGame)
-                .with_system(check_for_collisions)
-                .with_system(player_movement.before(check_for_collisions))
-                .with_system(ball_movement.before(check_for_collisions))
-                .with_system(tick_particles_lifetime)
-                .with_system(update_particles_size)
+                .with_system(player_movement)
+                .with_system(despawn_finished_bursts)
                 .with_system(change_game_state)
                 .with_system(update_score_board)
+                .with_system(update_lives_display)
                 .with_system(setup_resetable)
                 .with_system(game_over.before(setup_resetable))
+                .with_system(load_level.after(setup_resetable))
+                .with_system(check_level_cleared.before(load_level))
         )
         .add_system_set(SystemSet::on_update(AppState::Paused).with_system(change_game_state))
+        .add_system_set(SystemSet::on_enter(AppState::Win).with_system(enter_win_state))
+        .add_system_set(SystemSet::on_update(AppState::Win).with_system(restart_after_win))
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::on_update(AppState::InGame).with_system(collision_event_system),
+        )
         .run();
 }
 
@@ -88,27 +127,9 @@ struct Ball;
 #[derive(Component)]
 struct EnemyMarker;
 
-#[derive(Component, Deref, DerefMut)]
-struct Velocity(Vec2);
-
-#[derive(Component)]
-struct Collider;
-
-#[derive(Default)]
-struct CollisionEvent;
-
-#[derive(Bundle)]
-struct WallBundle {
-    sprite_bundle: SpriteBundle,
-    collider: Collider,
-}
-
 #[derive(Component)]
-struct ParticleMarker;
-
-#[derive(Component)]
-struct Particle {
-    lifetime: Timer,
+struct ParticleBurst {
+    timer: Timer,
 }
 
 #[derive(Component)]
@@ -119,65 +140,472 @@ struct ScoreboardCounter {
     counter: u32,
 }
 
+#[derive(Component)]
+struct LivesDisplay;
+
+#[derive(Resource)]
+struct Lives(u32);
+
+#[derive(Component)]
+struct WinMarker;
+
 #[derive(Component)]
 struct LavaMarker;
 
 #[derive(Default)]
 struct GameOverEvent;
 
+/// Fired by [`game_over`] when a life is lost but [`Lives`] hasn't run out yet,
+/// telling [`setup_resetable`] to respawn just the paddle and ball.
+#[derive(Default)]
+struct LifeLostEvent;
+
+/// Fired by [`game_over`] once [`Lives`] is exhausted (or by [`restart_after_win`]
+/// when the player restarts after a win), telling [`setup_resetable`] to rebuild
+/// the scoreboard/lives display and reload the level from scratch.
+#[derive(Default)]
+struct FullResetEvent;
+
 #[derive(Component)]
 struct Resetable;
 
-impl WallBundle {
-    fn new(pos: Vec2, scale: Vec2) -> WallBundle {
-        WallBundle {
-            sprite_bundle: SpriteBundle {
-                transform: Transform {
-                    translation: pos.extend(0.0),
-                    scale: scale.extend(1.0),
-                    ..default()
+#[derive(Component)]
+struct LevelMarker;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum LevelObjectKind {
+    #[default]
+    Wall,
+    Lava,
+}
+
+#[derive(Deserialize, Clone)]
+struct LevelObject {
+    kind: LevelObjectKind,
+    pos: [f32; 2],
+    size: [f32; 2],
+    color: [f32; 4],
+}
+
+#[derive(Deserialize, Clone)]
+struct LevelText {
+    value: String,
+    pos: [f32; 2],
+}
+
+#[derive(Deserialize, Clone)]
+struct LevelDef {
+    objects: Vec<LevelObject>,
+    #[serde(default)]
+    texts: Vec<LevelText>,
+    brick_color: [f32; 4],
+}
+
+#[derive(Resource)]
+struct Levels(Vec<LevelDef>);
+
+#[derive(Resource)]
+struct LevelId(u32);
+
+#[derive(Default)]
+struct LoadLevelEvent;
+
+#[derive(Resource)]
+struct BrickBreakEffect(Handle<EffectAsset>);
+
+fn build_brick_break_effect() -> EffectAsset {
+    let mut color_gradient = Gradient::new();
+    color_gradient.add_key(0.0, Vec4::from(PARTICLE_COLOR.as_rgba_f32()));
+    color_gradient.add_key(
+        1.0,
+        Vec4::new(PARTICLE_COLOR.r(), PARTICLE_COLOR.g(), PARTICLE_COLOR.b(), 0.0),
+    );
+
+    let mut size_gradient = Gradient::new();
+    size_gradient.add_key(0.0, Vec2::splat(PARTICLE_SIZE.x));
+    size_gradient.add_key(1.0, Vec2::splat(PARTICLE_SIZE.x + SCALING_FACTOR * 6.0));
+
+    EffectAsset {
+        name: "brick_break".to_string(),
+        capacity: 32768,
+        spawner: Spawner::once(Value::Uniform((2.0, 7.0)), true),
+        ..default()
+    }
+    .init(PositionCircleModifier {
+        axis: Vec3::Z,
+        radius: 5.0,
+        speed: 80.0.into(),
+        dimension: ShapeDimension::Volume,
+        ..default()
+    })
+    .init(ParticleLifetimeModifier {
+        lifetime: PARTICLE_LIFETIME as f32 / 1000.0,
+    })
+    .render(ColorOverLifetimeModifier {
+        gradient: color_gradient,
+    })
+    .render(SizeOverLifetimeModifier {
+        gradient: size_gradient,
+    })
+}
+
+/// A tone oscillator run through a short ADSR-shaped amplitude envelope,
+/// rendered as a one-shot sound effect so it can be retriggered on every
+/// collision without clicking.
+fn tone_with_envelope(freq: f32) -> impl AudioUnit32 {
+    (sine_hz(freq) * envelope(adsr_envelope)) >> split::<U2>()
+}
+
+fn adsr_envelope(t: f32) -> f32 {
+    const ATTACK: f32 = 0.01;
+    const DECAY: f32 = 0.08;
+    const SUSTAIN_LEVEL: f32 = 0.4;
+    const RELEASE_START: f32 = 0.15;
+    const RELEASE: f32 = 0.1;
+
+    if t < ATTACK {
+        t / ATTACK
+    } else if t < ATTACK + DECAY {
+        1.0 - (1.0 - SUSTAIN_LEVEL) * (t - ATTACK) / DECAY
+    } else if t < RELEASE_START {
+        SUSTAIN_LEVEL
+    } else if t < RELEASE_START + RELEASE {
+        SUSTAIN_LEVEL * (1.0 - (t - RELEASE_START) / RELEASE)
+    } else {
+        0.0
+    }
+}
+
+fn brick_tone_0() -> impl AudioUnit32 {
+    tone_with_envelope(BRICK_TONE_FREQS[0])
+}
+fn brick_tone_1() -> impl AudioUnit32 {
+    tone_with_envelope(BRICK_TONE_FREQS[1])
+}
+fn brick_tone_2() -> impl AudioUnit32 {
+    tone_with_envelope(BRICK_TONE_FREQS[2])
+}
+fn brick_tone_3() -> impl AudioUnit32 {
+    tone_with_envelope(BRICK_TONE_FREQS[3])
+}
+fn brick_tone_4() -> impl AudioUnit32 {
+    tone_with_envelope(BRICK_TONE_FREQS[4])
+}
+fn brick_tone_5() -> impl AudioUnit32 {
+    tone_with_envelope(BRICK_TONE_FREQS[5])
+}
+
+fn paddle_bounce_tone() -> impl AudioUnit32 {
+    tone_with_envelope(PADDLE_BOUNCE_FREQ)
+}
+
+/// Plays the brick tone matching `row_fraction` (0.0 = bottom row, 1.0 = top row).
+fn play_brick_tone(
+    row_fraction: f32,
+    audio: &mut Audio<AudioSource>,
+    assets: &mut Assets<AudioSource>,
+    dsp_manager: &DspManager,
+) {
+    let bucket = ((row_fraction * BRICK_TONE_FREQS.len() as f32) as usize)
+        .min(BRICK_TONE_FREQS.len() - 1);
+
+    let source = match bucket {
+        0 => dsp_manager.get_graph(brick_tone_0),
+        1 => dsp_manager.get_graph(brick_tone_1),
+        2 => dsp_manager.get_graph(brick_tone_2),
+        3 => dsp_manager.get_graph(brick_tone_3),
+        4 => dsp_manager.get_graph(brick_tone_4),
+        _ => dsp_manager.get_graph(brick_tone_5),
+    }
+    .expect("brick tone should be registered with DspPlugin");
+
+    audio.play_dsp(assets, source);
+}
+
+fn load_levels() -> Levels {
+    let mut level_paths: Vec<_> = fs::read_dir(LEVELS_DIR)
+        .expect("assets/levels directory should exist")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    level_paths.sort();
+
+    let levels = level_paths
+        .iter()
+        .map(|path| {
+            let contents = fs::read_to_string(path)
+                .unwrap_or_else(|e| panic!("failed to read level {:?}: {}", path, e));
+            serde_json::from_str::<LevelDef>(&contents)
+                .unwrap_or_else(|e| panic!("failed to parse level {:?}: {}", path, e))
+        })
+        .collect();
+
+    Levels(levels)
+}
+
+fn spawn_level(commands: &mut Commands, level: &LevelDef, asset_server: &AssetServer) {
+    for object in &level.objects {
+        let color = Color::rgba(object.color[0], object.color[1], object.color[2], object.color[3]);
+        let transform = Transform {
+            translation: Vec3::new(object.pos[0], object.pos[1], 0.0),
+            scale: Vec3::new(object.size[0], object.size[1], 1.0),
+            ..default()
+        };
+        let sprite_bundle = SpriteBundle {
+            transform,
+            sprite: Sprite { color, ..default() },
+            ..default()
+        };
+
+        let collider = Collider::cuboid(object.size[0] / 2.0, object.size[1] / 2.0);
+
+        match object.kind {
+            LevelObjectKind::Wall => {
+                commands.spawn((
+                    sprite_bundle,
+                    RigidBody::Fixed,
+                    collider,
+                    LevelMarker,
+                    Name::new("Wall"),
+                ));
+            }
+            LevelObjectKind::Lava => {
+                commands.spawn((
+                    sprite_bundle,
+                    collider,
+                    Sensor,
+                    ActiveEvents::COLLISION_EVENTS,
+                    LavaMarker,
+                    LevelMarker,
+                    Name::new("Lava"),
+                ));
+            }
+        }
+    }
+
+    for text in &level.texts {
+        commands.spawn((
+            TextBundle::from_section(
+                text.value.clone(),
+                TextStyle {
+                    font: asset_server.load("fonts/Roboto-Black.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
                 },
-                sprite: Sprite {
-                    color: WALL_COLOR,
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(text.pos[0]),
+                    top: Val::Px(text.pos[1]),
                     ..default()
                 },
                 ..default()
-            },
-            collider: Collider,
+            }),
+            LevelMarker,
+            Name::new("Level text"),
+        ));
+    }
+
+    let brick_color = Color::rgba(
+        level.brick_color[0],
+        level.brick_color[1],
+        level.brick_color[2],
+        level.brick_color[3],
+    );
+    spawn_bricks(commands, brick_grid_bounds(level), brick_color);
+}
+
+/// The rectangle within which [`spawn_bricks`] fills a brick grid.
+#[derive(Clone, Copy)]
+struct BrickGridBounds {
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+}
+
+/// Derives the grid bounds from the level's own wall objects (falling back to
+/// the default arena constants for any wall a level doesn't define), so a
+/// level author moving a wall in JSON also moves where the bricks fill in.
+/// The bottom edge isn't taken from a wall: levels have no floor wall, so that edge
+/// stays anchored to the paddle's fixed resting height.
+fn brick_grid_bounds(level: &LevelDef) -> BrickGridBounds {
+    let mut left = LEFT_WALL + GAP_BETWEEN_BRICKS_AND_SIDES;
+    let mut right = RIGHT_WALL - GAP_BETWEEN_BRICKS_AND_SIDES;
+    let top = level
+        .objects
+        .iter()
+        .filter(|object| object.kind == LevelObjectKind::Wall && object.pos[0] == 0.0)
+        .map(|object| object.pos[1] - object.size[1] / 2.0 - GAP_BETWEEN_BRICKS_AND_CEILING)
+        .reduce(f32::min)
+        .unwrap_or(TOP_WALL - GAP_BETWEEN_BRICKS_AND_CEILING);
+
+    for object in &level.objects {
+        if object.kind != LevelObjectKind::Wall {
+            continue;
+        }
+
+        if object.pos[0] < 0.0 {
+            left = object.pos[0] + object.size[0] / 2.0 + GAP_BETWEEN_BRICKS_AND_SIDES;
+        } else if object.pos[0] > 0.0 {
+            right = object.pos[0] - object.size[0] / 2.0 - GAP_BETWEEN_BRICKS_AND_SIDES;
         }
     }
+
+    BrickGridBounds {
+        left,
+        right,
+        bottom: BOTTOM_WALL + GAP_BETWEEN_PLAYER_AND_FLOOR + GAP_BETWEEN_PADDLE_AND_BRICKS,
+        top,
+    }
 }
 
-#[derive(Bundle)]
-struct Enemy {
-    sprite_bundle: SpriteBundle,
-    collider: Collider,
-    enemy_marker: EnemyMarker,
-    name: Name,
-    reset: Resetable,
-}
-
-impl Enemy {
-    fn new(pos: Vec3, enemy_id: i32) -> Enemy {
-        let mut s = String::from("Enemy ");
-        s.push_str(&enemy_id.to_string());
-        Enemy {
-            sprite_bundle: SpriteBundle {
-                transform: Transform {
-                    translation: pos,
-                    scale: ENEMY_SIZE,
-                    ..default()
-                },
-                sprite: Sprite {
-                    color: ENEMY_COLOR,
+/// The row/column count and starting offset for a brick grid, computed by
+/// [`compute_brick_grid_layout`] and consumed by [`spawn_bricks`].
+struct BrickGridLayout {
+    n_columns: usize,
+    n_rows: usize,
+    offset_x: f32,
+    offset_y: f32,
+}
+
+/// Computes how many bricks of [`BRICK_SIZE`] fit inside `bounds` (with
+/// [`GAP_BETWEEN_BRICKS`] between neighbours) and where to start placing them
+/// so the resulting grid is centered, instead of relying on a fixed
+/// row/column count.
+fn compute_brick_grid_layout(bounds: BrickGridBounds) -> BrickGridLayout {
+    let total_width = bounds.right - bounds.left;
+    let total_height = bounds.top - bounds.bottom;
+
+    let n_columns = (total_width / (BRICK_SIZE.x + GAP_BETWEEN_BRICKS)).floor() as usize;
+    let n_rows = (total_height / (BRICK_SIZE.y + GAP_BETWEEN_BRICKS)).floor() as usize;
+
+    let bricks_width =
+        n_columns as f32 * BRICK_SIZE.x + n_columns.saturating_sub(1) as f32 * GAP_BETWEEN_BRICKS;
+    let bricks_height =
+        n_rows as f32 * BRICK_SIZE.y + n_rows.saturating_sub(1) as f32 * GAP_BETWEEN_BRICKS;
+
+    let offset_x = bounds.left + (total_width - bricks_width) / 2.0 + BRICK_SIZE.x / 2.0;
+    let offset_y = bounds.bottom + (total_height - bricks_height) / 2.0 + BRICK_SIZE.y / 2.0;
+
+    BrickGridLayout { n_columns, n_rows, offset_x, offset_y }
+}
+
+fn spawn_bricks(commands: &mut Commands, bounds: BrickGridBounds, color: Color) {
+    let layout = compute_brick_grid_layout(bounds);
+
+    for row in 0..layout.n_rows {
+        for column in 0..layout.n_columns {
+            let translation = Vec3::new(
+                layout.offset_x + column as f32 * (BRICK_SIZE.x + GAP_BETWEEN_BRICKS),
+                layout.offset_y + row as f32 * (BRICK_SIZE.y + GAP_BETWEEN_BRICKS),
+                0.0,
+            );
+
+            commands.spawn((
+                SpriteBundle {
+                    transform: Transform {
+                        translation,
+                        scale: BRICK_SIZE.extend(1.0),
+                        ..default()
+                    },
+                    sprite: Sprite { color, ..default() },
                     ..default()
                 },
-                ..default()
-            },
-            collider: Collider,
-            enemy_marker: EnemyMarker,
-            name: Name::new(s),
-            reset: Resetable,
+                RigidBody::Fixed,
+                Collider::cuboid(BRICK_SIZE.x / 2.0, BRICK_SIZE.y / 2.0),
+                ActiveEvents::COLLISION_EVENTS,
+                EnemyMarker,
+                LevelMarker,
+                Name::new(format!("Enemy {row}-{column}")),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod brick_grid_tests {
+    use super::*;
+
+    #[test]
+    fn fits_the_expected_column_and_row_count() {
+        let bounds = BrickGridBounds { left: -200.0, right: 200.0, bottom: -100.0, top: 100.0 };
+        let layout = compute_brick_grid_layout(bounds);
+
+        // width 400 / (100 + 5) per column -> 3 columns fit, with room left over
+        assert_eq!(layout.n_columns, 3);
+        // height 200 / (30 + 5) per row -> 5 rows fit, with room left over
+        assert_eq!(layout.n_rows, 5);
+    }
+
+    #[test]
+    fn smaller_bounds_fit_fewer_bricks() {
+        let bounds = BrickGridBounds { left: -105.0, right: 105.0, bottom: -35.0, top: 35.0 };
+        let layout = compute_brick_grid_layout(bounds);
+
+        assert_eq!(layout.n_columns, 2);
+        assert_eq!(layout.n_rows, 2);
+    }
+
+    #[test]
+    fn grid_is_centered_in_its_bounds() {
+        let bounds = BrickGridBounds { left: -200.0, right: 200.0, bottom: -100.0, top: 100.0 };
+        let layout = compute_brick_grid_layout(bounds);
+
+        let left_margin = (layout.offset_x - BRICK_SIZE.x / 2.0) - bounds.left;
+        let grid_right_edge = layout.offset_x
+            + (layout.n_columns - 1) as f32 * (BRICK_SIZE.x + GAP_BETWEEN_BRICKS)
+            + BRICK_SIZE.x / 2.0;
+        let right_margin = bounds.right - grid_right_edge;
+        assert!((left_margin - right_margin).abs() < f32::EPSILON);
+
+        let bottom_margin = (layout.offset_y - BRICK_SIZE.y / 2.0) - bounds.bottom;
+        let grid_top_edge = layout.offset_y
+            + (layout.n_rows - 1) as f32 * (BRICK_SIZE.y + GAP_BETWEEN_BRICKS)
+            + BRICK_SIZE.y / 2.0;
+        let top_margin = bounds.top - grid_top_edge;
+        assert!((bottom_margin - top_margin).abs() < f32::EPSILON);
+    }
+}
+
+fn load_level(
+    mut commands: Commands,
+    levels: Res<Levels>,
+    level_id: Res<LevelId>,
+    asset_server: Res<AssetServer>,
+    mut ev_load_level: EventReader<LoadLevelEvent>,
+) {
+    for _ in ev_load_level.iter() {
+        let level = levels
+            .0
+            .get(level_id.0 as usize)
+            .unwrap_or_else(|| panic!("no level with id {}", level_id.0));
+        spawn_level(&mut commands, level, &asset_server);
+    }
+}
+
+fn check_level_cleared(
+    mut commands: Commands,
+    enemy_query: Query<&EnemyMarker>,
+    level_marker_query: Query<Entity, With<LevelMarker>>,
+    levels: Res<Levels>,
+    mut level_id: ResMut<LevelId>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    if enemy_query.is_empty() && level_marker_query.iter().next().is_some() {
+        for entity in &level_marker_query {
+            commands.entity(entity).despawn();
+        }
+
+        if level_id.0 + 1 >= levels.0.len() as u32 {
+            app_state.set(AppState::Win).unwrap();
+        } else {
+            level_id.0 += 1;
+            ev_load_level.send_default();
         }
     }
 }
@@ -186,44 +614,36 @@ fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut effects: ResMut<Assets<EffectAsset>>,
     asset_server: Res<AssetServer>,
 ) {
     // Camera
     commands.spawn(Camera2dBundle::default());
 
-    // Walls
-    commands.spawn(WallBundle::new(
-        Vec2::new(LEFT_WALL, 0.0),
-        Vec2::new(WALL_THICKNESS, (TOP_WALL - BOTTOM_WALL) + WALL_THICKNESS),
-    ));
-    commands.spawn(WallBundle::new(
-        Vec2::new(RIGHT_WALL, 0.0),
-        Vec2::new(WALL_THICKNESS, (TOP_WALL - BOTTOM_WALL) + WALL_THICKNESS),
-    ));
-    //commands.spawn(WallBundle::new(Vec2::new(0.0, BOTTOM_WALL), Vec2::new((RIGHT_WALL - LEFT_WALL) + WALL_THICKNESS, WALL_THICKNESS)));
-    commands.spawn(WallBundle::new(
-        Vec2::new(0.0, TOP_WALL),
-        Vec2::new((RIGHT_WALL - LEFT_WALL) + WALL_THICKNESS, WALL_THICKNESS),
+    commands.insert_resource(BrickBreakEffect(
+        effects.add(build_brick_break_effect()),
     ));
 
-    commands.spawn((
-        SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(0.0, -340.5, 0.0),
-                scale: Vec3::new(1296.8, 40.2, 1.0),
-                ..default()
-            },
-            sprite: Sprite {
-                color: Color::rgb(1.0, 0.66, 0.38),
-                ..default()
-            },
-            ..default()
-        },
-        Collider,
-        LavaMarker,
-        Name::new("Lava"),
-    ));
+    spawn_paddle_and_ball(&mut commands, &mut meshes, &mut materials);
+    spawn_scoreboard_and_lives(&mut commands, &asset_server, STARTING_LIVES);
+
+    commands.insert_resource(ScoreboardCounter { counter: 0 });
+    commands.insert_resource(Lives(STARTING_LIVES));
+
+    // Levels
+    let levels = load_levels();
+    let level_id = LevelId(0);
+    let level = &levels.0[level_id.0 as usize];
+    spawn_level(&mut commands, level, &asset_server);
+    commands.insert_resource(levels);
+    commands.insert_resource(level_id);
+}
 
+fn spawn_paddle_and_ball(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+) {
     // Paddle
     let paddle_y = BOTTOM_WALL + GAP_BETWEEN_PLAYER_AND_FLOOR;
     commands.spawn((
@@ -240,7 +660,9 @@ fn setup(
             ..default()
         },
         Paddle,
-        Collider,
+        RigidBody::Fixed,
+        Collider::cuboid(PLAYER_SIZE.x / 2.0, PLAYER_SIZE.y / 2.0),
+        ActiveEvents::COLLISION_EVENTS,
         Resetable,
         Name::new("Player"),
     ));
@@ -255,24 +677,21 @@ fn setup(
         },
         Ball,
         Resetable,
-        Velocity(INITIAL_BALL_DIRECTION.normalize() * BALL_SPEED),
+        RigidBody::Dynamic,
+        Velocity::linear(INITIAL_BALL_DIRECTION.normalize() * BALL_SPEED),
+        Collider::ball(BALL_SIZE.x / 2.0),
+        Restitution {
+            coefficient: 1.0,
+            combine_rule: CoefficientCombineRule::Max,
+        },
+        Friction::coefficient(0.0),
+        GravityScale(0.0),
+        Ccd::enabled(),
         Name::new("Ball"),
     ));
+}
 
-    //enemies
-    for j in 0..8 {
-        for i in 0..9 {
-            commands.spawn(Enemy::new(
-                Vec3::new(
-                    STARTING_ENEMY_POSITION.x + (90.0 * i as f32),
-                    STARTING_ENEMY_POSITION.y - (40.0 * j as f32),
-                    STARTING_ENEMY_POSITION.z,
-                ),
-                i + (j * 9),
-            ));
-        }
-    }
-
+fn spawn_scoreboard_and_lives(commands: &mut Commands, asset_server: &AssetServer, lives: u32) {
     commands.spawn((
         // Create a TextBundle that has a Text with a single section.
         TextBundle::from_section(
@@ -297,10 +716,29 @@ fn setup(
             ..default()
         }),
         Scoreboard,
-        Resetable,
     ));
 
-    commands.insert_resource(ScoreboardCounter { counter: 0 });
+    commands.spawn((
+        TextBundle::from_section(
+            format!("Lives: {lives}"),
+            TextStyle {
+                font: asset_server.load("fonts/Roboto-Black.ttf"),
+                font_size: 30.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_alignment(TextAlignment::TOP_LEFT)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            ..default()
+        }),
+        LivesDisplay,
+    ));
 }
 
 fn setup_resetable(
@@ -308,87 +746,24 @@ fn setup_resetable(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
-    mut ev_game_over: EventReader<GameOverEvent>,
+    lives: Res<Lives>,
+    mut ev_life_lost: EventReader<LifeLostEvent>,
+    mut ev_full_reset: EventReader<FullResetEvent>,
+    mut ev_load_level: EventWriter<LoadLevelEvent>,
 ) {
-    for _ in ev_game_over.iter() {
-        // Paddle
-        let paddle_y = BOTTOM_WALL + GAP_BETWEEN_PLAYER_AND_FLOOR;
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform {
-                    translation: Vec3::new(0.0, paddle_y, 0.0),
-                    scale: PLAYER_SIZE,
-                    ..default()
-                },
-                sprite: Sprite {
-                    color: PLAYER_COLOR,
-                    ..default()
-                },
-                ..default()
-            },
-            Paddle,
-            Collider,
-            Resetable,
-            Name::new("Player"),
-        ));
-
-        // Ball
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::default().into()).into(),
-                material: materials.add(ColorMaterial::from(BALL_COLOR)),
-                transform: Transform::from_translation(BALL_STARTING_POSITION)
-                    .with_scale(BALL_SIZE),
-                ..default()
-            },
-            Ball,
-            Resetable,
-            Velocity(INITIAL_BALL_DIRECTION.normalize() * BALL_SPEED),
-            Name::new("Ball"),
-        ));
-
-        //enemies
-        for j in 0..8 {
-            for i in 0..9 {
-                commands.spawn(Enemy::new(
-                    Vec3::new(
-                        STARTING_ENEMY_POSITION.x + (90.0 * i as f32),
-                        STARTING_ENEMY_POSITION.y - (40.0 * j as f32),
-                        STARTING_ENEMY_POSITION.z,
-                    ),
-                    i + (j * 9),
-                ));
-            }
-        }
-
-        commands.spawn((
-            // Create a TextBundle that has a Text with a single section.
-            TextBundle::from_section(
-                // Accepts a `String` or any type that converts into a `String`, such as `&str`
-                "100.0",
-                TextStyle {
-                    font: asset_server.load("fonts/Roboto-Black.ttf"),
-                    font_size: 50.0,
-                    color: Color::WHITE,
-                },
-            ) // Set the alignment of the Text
-            .with_text_alignment(TextAlignment::TOP_CENTER)
-            // Set the style of the TextBundle itself.
-            .with_style(Style {
-                position_type: PositionType::Absolute,
-                position: UiRect {
-                    bottom: Val::Px(5.0),
-                    left: Val::Px(1130.0),
-                    top: Val::Px(0.0),
-                    ..default()
-                },
-                ..default()
-            }),
-            Scoreboard,
-            Resetable,
-        ));
+    // A life was lost but the player still has lives left: only the
+    // paddle and ball need respawning, the board stays as it is.
+    for _ in ev_life_lost.iter() {
+        spawn_paddle_and_ball(&mut commands, &mut meshes, &mut materials);
+    }
 
+    // Lives ran out (or the player restarted after winning): rebuild the
+    // whole board from a fresh level.
+    for _ in ev_full_reset.iter() {
+        spawn_paddle_and_ball(&mut commands, &mut meshes, &mut materials);
+        spawn_scoreboard_and_lives(&mut commands, &asset_server, lives.0);
         commands.insert_resource(ScoreboardCounter { counter: 0 });
+        ev_load_level.send_default();
     }
 }
 
@@ -420,82 +795,69 @@ fn player_movement(
     }
 }
 
-fn ball_movement(mut query: Query<(&mut Transform, &Velocity)>) {
-    for (mut transform, velocity) in &mut query {
-        transform.translation.x += velocity.x * TIME_STEP;
-        transform.translation.y += velocity.y * TIME_STEP;
-    }
+#[derive(SystemParam)]
+struct CollisionQueries<'w, 's> {
+    enemy_query: Query<'w, 's, &'static Transform, With<EnemyMarker>>,
+    lava_query: Query<'w, 's, (), With<LavaMarker>>,
+    paddle_query: Query<'w, 's, (), With<Paddle>>,
+    ball_query: Query<'w, 's, (), With<Ball>>,
 }
 
-fn check_for_collisions(
-    mut ball_query: Query<(&mut Velocity, &Transform), With<Ball>>,
-    collider_query: Query<
-        (
-            Entity,
-            &Transform,
-            Option<&EnemyMarker>,
-            Option<&LavaMarker>,
-        ),
-        With<Collider>,
-    >,
-    mut collision_events: EventWriter<CollisionEvent>,
+#[derive(SystemParam)]
+struct CollisionEffects<'w, 's> {
+    audio: ResMut<'w, Audio>,
+    audio_assets: ResMut<'w, Assets<AudioSource>>,
+    dsp_manager: Res<'w, DspManager>,
+    counter: ResMut<'w, ScoreboardCounter>,
+    ev_game_over: EventWriter<'w, 's, GameOverEvent>,
+    brick_break_effect: Res<'w, BrickBreakEffect>,
+}
+
+fn collision_event_system(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    mut counter: ResMut<ScoreboardCounter>,
-    mut ev_game_over: EventWriter<GameOverEvent>,
+    queries: CollisionQueries,
+    mut collision_events: EventReader<CollisionEvent>,
+    levels: Res<Levels>,
+    level_id: Res<LevelId>,
+    mut effects: CollisionEffects,
 ) {
-    for (mut ball_velocity, ball_transform) in ball_query.iter_mut() {
-        let ball_size = ball_transform.scale.truncate();
-
-        // check collision with walls
-        for (entity_id, transform, maybe_enemy, maybe_lava) in &collider_query {
-            let collision = collide(
-                ball_transform.translation,
-                ball_size,
-                transform.translation,
-                transform.scale.truncate(),
-            );
-            if let Some(collision) = collision {
-                // Sends a collision event so that other systems can react to the collision
-                collision_events.send_default();
-
-                //destroying enemies
-                if maybe_enemy.is_some() {
-                    commands.entity(entity_id).despawn();
-                    let music = asset_server.load("enemy_destroy.ogg");
-                    audio.play(music);
-                    spawn_particles(transform.translation, &mut commands);
-                    counter.counter += 50;
-                }
-
-                if maybe_lava.is_some() {
-                    ev_game_over.send_default();
-                }
-
-                // reflect the ball when it collides
-                let mut reflect_x = false;
-                let mut reflect_y = false;
-
-                // only reflect if the ball's velocity is going in the opposite direction of the
-                // collision
-                match collision {
-                    Collision::Left => reflect_x = ball_velocity.x > 0.0,
-                    Collision::Right => reflect_x = ball_velocity.x < 0.0,
-                    Collision::Top => reflect_y = ball_velocity.y < 0.0,
-                    Collision::Bottom => reflect_y = ball_velocity.y > 0.0,
-                    Collision::Inside => { /* do nothing */ }
-                }
-
-                // reflect velocity on the x-axis if we hit something on the x-axis
-                if reflect_x {
-                    ball_velocity.x = -ball_velocity.x;
-                }
-
-                // reflect velocity on the y-axis if we hit something on the y-axis
-                if reflect_y {
-                    ball_velocity.y = -ball_velocity.y;
-                }
+    let bounds = brick_grid_bounds(&levels.0[level_id.0 as usize]);
+
+    for event in collision_events.iter() {
+        let CollisionEvent::Started(e1, e2, _flags) = event else {
+            continue;
+        };
+
+        for (a, b) in [(*e1, *e2), (*e2, *e1)] {
+            if queries.ball_query.get(b).is_err() {
+                continue;
+            }
+
+            if let Ok(transform) = queries.enemy_query.get(a) {
+                commands.entity(a).despawn();
+                let row_fraction = ((transform.translation.y - bounds.bottom)
+                    / (bounds.top - bounds.bottom))
+                    .clamp(0.0, 1.0);
+                play_brick_tone(
+                    row_fraction,
+                    &mut effects.audio,
+                    &mut effects.audio_assets,
+                    &effects.dsp_manager,
+                );
+                spawn_particles(transform.translation, &mut commands, &effects.brick_break_effect);
+                effects.counter.counter += 50;
+            }
+
+            if queries.lava_query.get(a).is_ok() {
+                effects.ev_game_over.send_default();
+            }
+
+            if queries.paddle_query.get(a).is_ok() {
+                let source = effects
+                    .dsp_manager
+                    .get_graph(paddle_bounce_tone)
+                    .expect("paddle bounce tone should be registered with DspPlugin");
+                effects.audio.play_dsp(&mut effects.audio_assets, source);
             }
         }
     }
@@ -510,89 +872,150 @@ fn update_score_board(
     }
 }
 
-//diff x = 20, diff y = 10
-fn spawn_particles(pos: Vec3, commands: &mut Commands) {
-    let mut rng = rand::thread_rng();
-    let particle_count = rng.gen_range(2..7);
-
-    for _ in 0..particle_count {
-        let rand_x = rng.gen_range(pos.x - 30.0..pos.x + 30.0);
-        let rand_y = rng.gen_range(pos.y - 20.0..pos.y + 20.0);
-
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform {
-                    translation: Vec3::new(rand_x, rand_y, pos.z),
-                    scale: PARTICLE_SIZE,
-                    ..default()
-                },
-                sprite: Sprite {
-                    color: PARTICLE_COLOR,
-                    ..default()
-                },
-                ..default()
-            },
-            ParticleMarker,
-            Particle {
-                lifetime: Timer::new(Duration::from_millis(PARTICLE_LIFETIME), TimerMode::Once),
-            },
-            Name::new("Particle"),
-        ));
+fn update_lives_display(mut query: Query<&mut Text, With<LivesDisplay>>, lives: Res<Lives>) {
+    for mut text in &mut query {
+        text.sections[0].value = format!("Lives: {}", lives.0);
     }
+}
 
-    /*
-    FuseTime {
-                // create the non-repeating fuse timer
-                timer: Timer::new(Duration::from_secs(5), TimerMode::Once),
-            },
-    */
+fn spawn_particles(pos: Vec3, commands: &mut Commands, effect: &BrickBreakEffect) {
+    let bundle = ParticleEffectBundle {
+        transform: Transform::from_translation(pos),
+        ..ParticleEffectBundle::new(effect.0.clone())
+    };
+
+    commands.spawn((
+        bundle,
+        ParticleBurst {
+            timer: Timer::new(Duration::from_millis(PARTICLE_LIFETIME), TimerMode::Once),
+        },
+        Name::new("Particle burst"),
+    ));
 }
 
-fn tick_particles_lifetime(
+fn despawn_finished_bursts(
     time: Res<Time>,
     mut commands: Commands,
-    mut particle_query: Query<(Entity, &mut Particle), With<ParticleMarker>>,
+    mut burst_query: Query<(Entity, &mut ParticleBurst)>,
 ) {
-    for (entity, mut particle) in particle_query.iter_mut() {
-        particle.lifetime.tick(time.delta());
+    for (entity, mut burst) in burst_query.iter_mut() {
+        burst.timer.tick(time.delta());
 
-        if particle.lifetime.finished() {
+        if burst.timer.finished() {
             commands.entity(entity).despawn();
         }
     }
 }
 
-fn update_particles_size(mut particle_query: Query<&mut Transform, With<ParticleMarker>>) {
-    for mut trans in particle_query.iter_mut() {
-        trans.scale.x += SCALING_FACTOR;
-        trans.scale.y += SCALING_FACTOR;
-    }
-}
-
-fn change_game_state(keyboard_input: Res<Input<KeyCode>>, mut app_state: ResMut<State<AppState>>) {
+fn change_game_state(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
     if keyboard_input.just_pressed(KeyCode::Key1) {
         match app_state.current() {
-            AppState::InGame => app_state.set(AppState::Paused).unwrap(),
+            AppState::InGame => {
+                app_state.set(AppState::Paused).unwrap();
+                rapier_config.physics_pipeline_active = false;
+            }
             AppState::Paused => println!("nothing"),
+            AppState::Win => println!("nothing"),
         }
     }
 
     if keyboard_input.just_pressed(KeyCode::Key2) {
         match app_state.current() {
             AppState::InGame => println!("nothing"),
-            AppState::Paused => app_state.set(AppState::InGame).unwrap(),
+            AppState::Paused => {
+                app_state.set(AppState::InGame).unwrap();
+                rapier_config.physics_pipeline_active = true;
+            }
+            AppState::Win => println!("nothing"),
         }
     }
 }
 
 fn game_over(
     mut commands: Commands,
-    query: Query<Entity, With<Resetable>>,
+    paddle_and_ball_query: Query<Entity, Or<(With<Paddle>, With<Ball>)>>,
+    board_query: Query<Entity, Or<(With<Scoreboard>, With<LivesDisplay>, With<LevelMarker>)>>,
+    mut lives: ResMut<Lives>,
+    mut level_id: ResMut<LevelId>,
     mut ev_game_over: EventReader<GameOverEvent>,
+    mut ev_life_lost: EventWriter<LifeLostEvent>,
+    mut ev_full_reset: EventWriter<FullResetEvent>,
 ) {
     for _ in ev_game_over.iter() {
-        for entity in query.iter() {
+        for entity in &paddle_and_ball_query {
             commands.entity(entity).despawn();
         }
+
+        lives.0 = lives.0.saturating_sub(1);
+
+        if lives.0 == 0 {
+            for entity in &board_query {
+                commands.entity(entity).despawn();
+            }
+
+            lives.0 = STARTING_LIVES;
+            level_id.0 = 0;
+            ev_full_reset.send_default();
+        } else {
+            ev_life_lost.send_default();
+        }
+    }
+}
+
+fn enter_win_state(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    rapier_config.physics_pipeline_active = false;
+
+    commands.spawn((
+        TextBundle::from_section(
+            "You win! Press Enter to play again",
+            TextStyle {
+                font: asset_server.load("fonts/Roboto-Black.ttf"),
+                font_size: 50.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_text_alignment(TextAlignment::CENTER)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            align_self: AlignSelf::Center,
+            margin: UiRect::all(Val::Auto),
+            ..default()
+        }),
+        WinMarker,
+        Name::new("Win text"),
+    ));
+}
+
+fn restart_after_win(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut commands: Commands,
+    query: Query<
+        Entity,
+        Or<(With<WinMarker>, With<Paddle>, With<Ball>, With<Scoreboard>, With<LivesDisplay>)>,
+    >,
+    mut level_id: ResMut<LevelId>,
+    mut lives: ResMut<Lives>,
+    mut app_state: ResMut<State<AppState>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut ev_full_reset: EventWriter<FullResetEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        for entity in &query {
+            commands.entity(entity).despawn();
+        }
+
+        level_id.0 = 0;
+        lives.0 = STARTING_LIVES;
+        rapier_config.physics_pipeline_active = true;
+        app_state.set(AppState::InGame).unwrap();
+        ev_full_reset.send_default();
     }
 }
\ No newline at end of file